@@ -16,6 +16,7 @@
 
 pub mod errors;
 pub mod message;
+pub mod trace;
 
 pub use errors::ErrorType;
 pub use errors::ExternalError;
@@ -23,6 +24,8 @@ pub use errors::ExternalError;
 pub use errors::OrErr;
 pub use errors::{DownloadFromRemotePeerFailed, HTTPError};
 
+pub use trace::{Trace, TraceMode};
+
 // DFError is the error for dragonfly.
 #[derive(thiserror::Error, Debug)]
 pub enum DFError {
@@ -118,6 +121,11 @@ pub enum DFError {
     #[error("invalid parameter")]
     InvalidParameter,
 
+    // UnsupportedDigestAlgorithm is the error when the digest algorithm prefix of an
+    // `algo:hex` digest string is not recognized.
+    #[error("unsupported digest algorithm {0}")]
+    UnsupportedDigestAlgorithm(String),
+
     #[error(transparent)]
     Utf8(#[from] std::str::Utf8Error),
 
@@ -144,6 +152,152 @@ pub enum DFError {
     // ExternalError is the error for external error.
     #[error(transparent)]
     ExternalError(#[from] ExternalError),
+
+    // TooManyErrors is the error when every remote peer tried for a piece has failed,
+    // collecting each attempt's error instead of only the last one.
+    #[error("too many errors: {}", summarize_causes(.0))]
+    TooManyErrors(Vec<DFError>),
+}
+
+// summarize_causes renders the distinct underlying causes of a collection of errors,
+// deduplicated by their `Display` message, for use in `TooManyErrors`'s error message.
+fn summarize_causes(errors: &[DFError]) -> String {
+    let mut causes = Vec::new();
+    for error in errors {
+        let cause = error.to_string();
+        if !causes.contains(&cause) {
+            causes.push(cause);
+        }
+    }
+
+    causes.join(", ")
+}
+
+// GRPC_HTTP_STATUS_TABLE pairs each gRPC code this client maps with its corresponding
+// HTTP status, following the same convention as grpc-gateway. Deriving both directions
+// (`grpc_code_from_http_status` and `http_status_from_grpc_code`) from this single table
+// keeps the two maps from drifting apart and round-tripping differently.
+const GRPC_HTTP_STATUS_TABLE: &[(tonic::Code, http::StatusCode)] = &[
+    (tonic::Code::InvalidArgument, http::StatusCode::BAD_REQUEST),
+    (tonic::Code::Unauthenticated, http::StatusCode::UNAUTHORIZED),
+    (tonic::Code::PermissionDenied, http::StatusCode::FORBIDDEN),
+    (tonic::Code::NotFound, http::StatusCode::NOT_FOUND),
+    (tonic::Code::Aborted, http::StatusCode::CONFLICT),
+    (tonic::Code::OutOfRange, http::StatusCode::RANGE_NOT_SATISFIABLE),
+    (
+        tonic::Code::ResourceExhausted,
+        http::StatusCode::TOO_MANY_REQUESTS,
+    ),
+    (tonic::Code::Unimplemented, http::StatusCode::NOT_IMPLEMENTED),
+    (
+        tonic::Code::Unavailable,
+        http::StatusCode::SERVICE_UNAVAILABLE,
+    ),
+    (
+        tonic::Code::DeadlineExceeded,
+        http::StatusCode::GATEWAY_TIMEOUT,
+    ),
+    (tonic::Code::DataLoss, http::StatusCode::INTERNAL_SERVER_ERROR),
+];
+
+// grpc_code_from_http_status maps an upstream HTTP status code to the closest gRPC
+// status code, for `DFError::HTTP` so that a genuine 404/429/503/etc. from the HTTP
+// proxy path isn't flattened to `Unknown`.
+fn grpc_code_from_http_status(status: http::StatusCode) -> tonic::Code {
+    if let Some((code, _)) = GRPC_HTTP_STATUS_TABLE.iter().find(|(_, s)| *s == status) {
+        return *code;
+    }
+
+    if status.is_server_error() {
+        return tonic::Code::Internal;
+    }
+
+    tonic::Code::Unknown
+}
+
+// http_status_from_grpc_code maps a gRPC status code to its corresponding HTTP status,
+// for every `DFError` variant other than `HTTP` (which already carries a real upstream
+// status and is returned as-is by `http_status_code`).
+fn http_status_from_grpc_code(code: tonic::Code) -> http::StatusCode {
+    GRPC_HTTP_STATUS_TABLE
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, status)| *status)
+        .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+impl DFError {
+    // status_code returns the gRPC status code that best represents this error, so that
+    // callers serving a download over gRPC can translate internal failures into the
+    // correct wire-level status instead of falling back to `Unknown`.
+    pub fn status_code(&self) -> tonic::Code {
+        match self {
+            Self::TaskNotFound(_) | Self::PieceNotFound(_) | Self::HostNotFound(_) => {
+                tonic::Code::NotFound
+            }
+            Self::InvalidURI(_)
+            | Self::InvalidPeer(_)
+            | Self::InvalidParameter
+            | Self::InvalidContentLength
+            | Self::InvalidState(_)
+            | Self::InvalidStateTransition(_, _)
+            | Self::UnsupportedDigestAlgorithm(_) => tonic::Code::InvalidArgument,
+            Self::SendTimeout | Self::WaitForPieceFinishedTimeout(_) => {
+                tonic::Code::DeadlineExceeded
+            }
+            Self::Unimplemented => tonic::Code::Unimplemented,
+            Self::MaxScheduleCountExceeded(_) => tonic::Code::ResourceExhausted,
+            Self::AvailableManagerNotFound
+            | Self::AvailableSchedulersNotFound
+            | Self::SchedulerClientNotFound => tonic::Code::Unavailable,
+            Self::PieceDigestMismatch => tonic::Code::DataLoss,
+            Self::DownloadFromRemotePeerFailed(_) | Self::TooManyErrors(_) => {
+                tonic::Code::Unavailable
+            }
+            Self::TonicStatus(status) => status.code(),
+            Self::HTTP(http_error) => grpc_code_from_http_status(http_error.status),
+            _ => tonic::Code::Unknown,
+        }
+    }
+
+    // to_tonic_status converts the error into a `tonic::Status` carrying the mapped
+    // status code, so gRPC handlers can simply `.into()`/return it to the peer.
+    pub fn to_tonic_status(&self) -> tonic::Status {
+        tonic::Status::new(self.status_code(), self.to_string())
+    }
+
+    // http_status_code returns the HTTP status code that best represents this error, for
+    // use by the HTTP proxy when it cannot serve a download. `HTTP` already carries the
+    // upstream's real status code, so it's returned as-is instead of round-tripping it
+    // through a gRPC code, which would flatten statuses the table doesn't list, like
+    // 418/502/505, into the same 500 on the way back out.
+    pub fn http_status_code(&self) -> http::StatusCode {
+        if let Self::HTTP(http_error) = self {
+            return http_error.status;
+        }
+
+        http_status_from_grpc_code(self.status_code())
+    }
+
+    // is_retryable classifies whether retrying the operation that produced this error is
+    // worthwhile, so retry loops can decide whether to back off or give up.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::SendTimeout
+            | Self::WaitForPieceFinishedTimeout(_)
+            | Self::DownloadFromRemotePeerFailed(_)
+            | Self::AvailableSchedulersNotFound
+            | Self::AvailableManagerNotFound => true,
+            Self::HTTP(http_error) => http_error.status.is_server_error(),
+            Self::TooManyErrors(errors) => errors.iter().any(DFError::is_retryable),
+            Self::PieceDigestMismatch
+            | Self::InvalidURI(_)
+            | Self::InvalidParameter
+            | Self::UnsupportedDigestAlgorithm(_)
+            | Self::Unimplemented => false,
+            _ => false,
+        }
+    }
 }
 
 // SendError is the error for send.
@@ -184,4 +338,68 @@ mod tests {
         let err = do_sth_with_error().err().unwrap();
         assert_eq!(format!("{}", err), "StorageError cause: inner error");
     }
+
+    #[test]
+    fn should_map_dferror_to_status_code() {
+        assert_eq!(
+            DFError::TaskNotFound("task".to_string()).status_code(),
+            tonic::Code::NotFound
+        );
+        assert_eq!(
+            DFError::InvalidParameter.status_code(),
+            tonic::Code::InvalidArgument
+        );
+        assert_eq!(
+            DFError::MaxScheduleCountExceeded(3).http_status_code(),
+            http::StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            DFError::TooManyErrors(vec![DFError::SendTimeout]).status_code(),
+            tonic::Code::Unavailable
+        );
+    }
+
+    #[test]
+    fn should_map_http_status_to_grpc_code() {
+        assert_eq!(
+            grpc_code_from_http_status(http::StatusCode::NOT_FOUND),
+            tonic::Code::NotFound
+        );
+        assert_eq!(
+            grpc_code_from_http_status(http::StatusCode::TOO_MANY_REQUESTS),
+            tonic::Code::ResourceExhausted
+        );
+        assert_eq!(
+            grpc_code_from_http_status(http::StatusCode::BAD_GATEWAY),
+            tonic::Code::Internal
+        );
+        assert_eq!(
+            grpc_code_from_http_status(http::StatusCode::SERVICE_UNAVAILABLE),
+            tonic::Code::Unavailable
+        );
+    }
+
+    #[test]
+    fn should_round_trip_grpc_and_http_status() {
+        for &(code, status) in GRPC_HTTP_STATUS_TABLE {
+            assert_eq!(http_status_from_grpc_code(code), status);
+            assert_eq!(grpc_code_from_http_status(status), code);
+        }
+    }
+
+    #[test]
+    fn should_classify_retryable_errors() {
+        assert!(DFError::SendTimeout.is_retryable());
+        assert!(!DFError::InvalidParameter.is_retryable());
+
+        let aggregate = DFError::TooManyErrors(vec![
+            DFError::InvalidParameter,
+            DFError::SendTimeout,
+        ]);
+        assert!(aggregate.is_retryable());
+        assert_eq!(
+            aggregate.to_string(),
+            "too many errors: invalid parameter, send timeout"
+        );
+    }
 }
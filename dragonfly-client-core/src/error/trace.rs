@@ -0,0 +1,249 @@
+/*
+ *     Copyright 2024 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error as StdError;
+use tracing::error;
+
+use crate::error::{DFError, ExternalError};
+
+// TraceMode selects how the cause chain is rendered in a trace event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    // Compact renders the cause chain as a single `a -> b -> c` field, for subscribers
+    // that only need a quick summary.
+    Compact,
+
+    // Pretty emits each cause in the chain as its own structured event, carrying the
+    // cause's index and message as separate fields instead of one interpolated string.
+    Pretty,
+}
+
+// Trace is implemented by errors that can emit themselves as a structured tracing event,
+// so downstream subscribers can filter and aggregate failures by type and affected
+// resource instead of regex-matching message text.
+pub trait Trace: StdError {
+    // error_type returns the stable discriminant for this error, e.g. "TaskNotFound".
+    fn error_type(&self) -> &'static str;
+
+    // trace emits a `tracing` error event carrying `error.type` and `error.kind`, then
+    // emits the cause chain per `mode`. This default emits no resource identifiers;
+    // implementors whose variants embed them override it to add them as fields.
+    fn trace(&self, mode: TraceMode) {
+        error!(
+            error.r#type = self.error_type(),
+            error.kind = %self,
+            "error occurred",
+        );
+        trace_causes(self, mode, None);
+    }
+
+    // trace_with_request_id emits the same events as `trace`, additionally attaching
+    // `request.id` to every event so the error (and its cause chain) can be tied back
+    // to the download request that caused it, across scheduler RPCs, piece fetches,
+    // and log lines.
+    fn trace_with_request_id(&self, mode: TraceMode, request_id: &str) {
+        error!(
+            error.r#type = self.error_type(),
+            error.kind = %self,
+            request.id = request_id,
+            "error occurred",
+        );
+        trace_causes(self, mode, Some(request_id));
+    }
+}
+
+// collect_causes walks `err`'s `source()` chain and returns each cause's message, in
+// order from the immediate cause outward.
+fn collect_causes(err: &(impl StdError + ?Sized)) -> Vec<String> {
+    let mut causes = Vec::new();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    causes
+}
+
+// trace_causes emits `err`'s cause chain according to `mode`. `Pretty` emits one event
+// per cause with its own `error.cause.index`/`error.cause.message` fields, rather than
+// folding the whole chain into a single string field (which would both lose structure
+// and, for multi-line renderings, break single-line log formatters). `Compact` keeps
+// the chain as one joined summary field for subscribers that don't need the detail.
+fn trace_causes(err: &(impl StdError + ?Sized), mode: TraceMode, request_id: Option<&str>) {
+    let causes = collect_causes(err);
+    if causes.is_empty() {
+        return;
+    }
+
+    match mode {
+        TraceMode::Compact => {
+            let chain = causes.join(" -> ");
+            match request_id {
+                Some(request_id) => error!(
+                    error.chain = %chain,
+                    request.id = request_id,
+                    "error cause chain",
+                ),
+                None => error!(error.chain = %chain, "error cause chain"),
+            }
+        }
+        TraceMode::Pretty => {
+            for (index, cause) in causes.iter().enumerate() {
+                match request_id {
+                    Some(request_id) => error!(
+                        error.cause.index = index,
+                        error.cause.message = %cause,
+                        request.id = request_id,
+                        "error cause",
+                    ),
+                    None => error!(
+                        error.cause.index = index,
+                        error.cause.message = %cause,
+                        "error cause",
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl Trace for DFError {
+    fn error_type(&self) -> &'static str {
+        match self {
+            Self::IO(_) => "IO",
+            Self::MpscSend(_) => "MpscSend",
+            Self::SendTimeout => "SendTimeout",
+            Self::HashRing(_) => "HashRing",
+            Self::HostNotFound(_) => "HostNotFound",
+            Self::TaskNotFound(_) => "TaskNotFound",
+            Self::PieceNotFound(_) => "PieceNotFound",
+            Self::PieceStateIsFailed(_) => "PieceStateIsFailed",
+            Self::WaitForPieceFinishedTimeout(_) => "WaitForPieceFinishedTimeout",
+            Self::AvailableManagerNotFound => "AvailableManagerNotFound",
+            Self::AvailableSchedulersNotFound => "AvailableSchedulersNotFound",
+            Self::DownloadFromRemotePeerFailed(_) => "DownloadFromRemotePeerFailed",
+            Self::ColumnFamilyNotFound(_) => "ColumnFamilyNotFound",
+            Self::InvalidStateTransition(_, _) => "InvalidStateTransition",
+            Self::InvalidState(_) => "InvalidState",
+            Self::InvalidURI(_) => "InvalidURI",
+            Self::InvalidPeer(_) => "InvalidPeer",
+            Self::SchedulerClientNotFound => "SchedulerClientNotFound",
+            Self::UnexpectedResponse => "UnexpectedResponse",
+            Self::PieceDigestMismatch => "PieceDigestMismatch",
+            Self::MaxScheduleCountExceeded(_) => "MaxScheduleCountExceeded",
+            Self::InvalidContentLength => "InvalidContentLength",
+            Self::InvalidParameter => "InvalidParameter",
+            Self::UnsupportedDigestAlgorithm(_) => "UnsupportedDigestAlgorithm",
+            Self::Utf8(_) => "Utf8",
+            Self::Unknown(_) => "Unknown",
+            Self::Unimplemented => "Unimplemented",
+            Self::EmptyHTTPRangeError => "EmptyHTTPRangeError",
+            Self::TonicStatus(_) => "TonicStatus",
+            Self::HTTP(_) => "HTTP",
+            Self::ExternalError(_) => "ExternalError",
+            Self::TooManyErrors(_) => "TooManyErrors",
+        }
+    }
+
+    // trace overrides the default implementation to additionally emit the resource
+    // identifier already embedded in the variant as its own field, instead of letting
+    // it stay interpolated into the `error.kind` message.
+    fn trace(&self, mode: TraceMode) {
+        match self {
+            Self::TaskNotFound(task_id) => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                task.id = %task_id,
+                "error occurred",
+            ),
+            Self::PieceNotFound(piece_id) => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                piece.id = %piece_id,
+                "error occurred",
+            ),
+            Self::HostNotFound(host_id) => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                host.id = %host_id,
+                "error occurred",
+            ),
+            _ => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                "error occurred",
+            ),
+        }
+        trace_causes(self, mode, None);
+    }
+
+    // trace_with_request_id mirrors `trace`'s per-variant resource fields, additionally
+    // attaching `request.id` so a `TaskNotFound`/`PieceNotFound`/`HostNotFound` emitted
+    // during a request doesn't silently lose its `task.id`/`piece.id`/`host.id` just
+    // because a request id was attached.
+    fn trace_with_request_id(&self, mode: TraceMode, request_id: &str) {
+        match self {
+            Self::TaskNotFound(task_id) => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                task.id = %task_id,
+                request.id = request_id,
+                "error occurred",
+            ),
+            Self::PieceNotFound(piece_id) => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                piece.id = %piece_id,
+                request.id = request_id,
+                "error occurred",
+            ),
+            Self::HostNotFound(host_id) => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                host.id = %host_id,
+                request.id = request_id,
+                "error occurred",
+            ),
+            _ => error!(
+                error.r#type = self.error_type(),
+                error.kind = %self,
+                request.id = request_id,
+                "error occurred",
+            ),
+        }
+        trace_causes(self, mode, Some(request_id));
+    }
+}
+
+impl Trace for ExternalError {
+    fn error_type(&self) -> &'static str {
+        "ExternalError"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_walk_cause_chain() {
+        let inner_error = std::io::Error::new(std::io::ErrorKind::Other, "inner error");
+        let err = ExternalError::new(crate::error::ErrorType::StorageError)
+            .with_cause(inner_error.into());
+
+        assert_eq!(collect_causes(&err), vec!["inner error".to_string()]);
+    }
+}
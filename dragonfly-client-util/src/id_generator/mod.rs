@@ -15,13 +15,107 @@
  */
 
 use dragonfly_client_core::{
-    error::{ErrorType, OrErr},
+    error::{DFError, ErrorType, OrErr},
     Result,
 };
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha512};
 use url::Url;
 use uuid::Uuid;
 
+// DigestAlgorithm is the content-hash algorithm used to incorporate a caller-supplied
+// digest into the task id, and to hash the task id's own components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DigestAlgorithm {
+    // Sha256 is the SHA-256 digest algorithm.
+    #[default]
+    Sha256,
+
+    // Sha512 is the SHA-512 digest algorithm.
+    Sha512,
+
+    // Blake3 is the BLAKE3 digest algorithm.
+    Blake3,
+}
+
+impl DigestAlgorithm {
+    // name returns the algorithm's canonical prefix, as used in `algo:hex` digest strings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    // hex_len returns the length of a valid hex-encoded digest for this algorithm.
+    pub fn hex_len(&self) -> usize {
+        match self {
+            Self::Sha256 => 64,
+            Self::Sha512 => 128,
+            Self::Blake3 => 64,
+        }
+    }
+
+    // parse parses an `algo:hex` digest string (e.g. `sha256:...`, `blake3:...`),
+    // validating that the hex length matches the named algorithm before returning it.
+    pub fn parse(digest: &str) -> Result<(Self, &str)> {
+        let (name, hex) = digest
+            .split_once(':')
+            .ok_or_else(|| DFError::UnsupportedDigestAlgorithm(digest.to_string()))?;
+
+        let algorithm = match name {
+            "sha256" => Self::Sha256,
+            "sha512" => Self::Sha512,
+            "blake3" => Self::Blake3,
+            _ => return Err(DFError::UnsupportedDigestAlgorithm(name.to_string())),
+        };
+
+        if hex.len() != algorithm.hex_len() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(DFError::PieceDigestMismatch);
+        }
+
+        Ok((algorithm, hex))
+    }
+
+    // hasher returns a fresh `Hasher` for this algorithm.
+    fn hasher(&self) -> Hasher {
+        match self {
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            Self::Sha512 => Hasher::Sha512(Sha512::new()),
+            Self::Blake3 => Hasher::Blake3(blake3::Hasher::new()),
+        }
+    }
+}
+
+// Hasher wraps the hasher type for each supported `DigestAlgorithm` behind a common
+// update/finalize interface, since `sha2::Digest` and `blake3::Hasher` are not
+// interchangeable.
+enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(blake3::Hasher),
+}
+
+impl Hasher {
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        match self {
+            Self::Sha256(hasher) => Digest::update(hasher, data.as_ref()),
+            Self::Sha512(hasher) => Digest::update(hasher, data.as_ref()),
+            Self::Blake3(hasher) => {
+                hasher.update(data.as_ref());
+            }
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            Self::Sha256(hasher) => hex::encode(hasher.finalize()),
+            Self::Sha512(hasher) => hex::encode(hasher.finalize()),
+            Self::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
 // IDGenerator is used to generate the id for the resources.
 #[derive(Debug)]
 pub struct IDGenerator {
@@ -33,6 +127,10 @@ pub struct IDGenerator {
 
     // is_seed_peer indicates whether the host is a seed peer.
     is_seed_peer: bool,
+
+    // digest_algorithm is the content-hash algorithm used to generate task ids, so that
+    // the same instance produces consistent task ids across calls.
+    digest_algorithm: DigestAlgorithm,
 }
 
 // IDGenerator implements the IDGenerator.
@@ -43,9 +141,33 @@ impl IDGenerator {
             ip,
             hostname,
             is_seed_peer,
+            digest_algorithm: DigestAlgorithm::default(),
+        }
+    }
+
+    // with_digest_algorithm creates a new IDGenerator that hashes task ids with
+    // `digest_algorithm` instead of the default SHA-256, so the client can interoperate
+    // with registries and artifact sources that advertise non-SHA256 content digests.
+    pub fn with_digest_algorithm(
+        ip: String,
+        hostname: String,
+        is_seed_peer: bool,
+        digest_algorithm: DigestAlgorithm,
+    ) -> Self {
+        IDGenerator {
+            ip,
+            hostname,
+            is_seed_peer,
+            digest_algorithm,
         }
     }
 
+    // digest_algorithm returns the content-hash algorithm this instance generates task
+    // ids with.
+    pub fn digest_algorithm(&self) -> DigestAlgorithm {
+        self.digest_algorithm
+    }
+
     // host_id generates the host id.
     pub fn host_id(&self) -> String {
         if self.is_seed_peer {
@@ -73,14 +195,24 @@ impl IDGenerator {
         let mut artifact_url = url.clone();
         artifact_url.query_pairs_mut().clear().extend_pairs(query);
 
-        // Initialize the hasher.
-        let mut hasher = Sha256::new();
+        // Initialize the hasher with the configured digest algorithm.
+        let mut hasher = self.digest_algorithm.hasher();
 
         // Add the url to generate the task id.
         hasher.update(artifact_url.to_string());
 
-        // Add the digest to generate the task id.
+        // Add the digest to generate the task id. A digest with a recognized `algo:hex`
+        // prefix is validated up front so a malformed or unsupported one is rejected
+        // early, but the *whole* original string (prefix included) is still what gets
+        // hashed, not just the hex part — this keeps task ids stable for callers
+        // upgrading from a client version that didn't understand algorithm prefixes at
+        // all and just folded the raw digest string into the hash. A digest with no
+        // colon is treated as an opaque legacy digest and hashed as-is, unvalidated,
+        // same as before this change.
         if let Some(digest) = digest {
+            if digest.contains(':') {
+                DigestAlgorithm::parse(digest)?;
+            }
             hasher.update(digest);
         }
 
@@ -98,7 +230,23 @@ impl IDGenerator {
         hasher.update(piece_length.to_string());
 
         // Generate the task id.
-        Ok(hex::encode(hasher.finalize()))
+        Ok(hasher.finalize_hex())
+    }
+
+    // request_id generates a unique, sortable identifier for a single download request,
+    // so that a `dfget`/proxy request can be correlated across scheduler RPCs, piece
+    // fetches, and log lines.
+    pub fn request_id(&self) -> String {
+        Uuid::now_v7().to_string()
+    }
+
+    // correlation_id returns `request_id` if the caller already has one, e.g. forwarded
+    // from an incoming request header, or falls back to a freshly generated `request_id`
+    // when none is present.
+    pub fn correlation_id(&self, request_id: Option<&str>) -> String {
+        request_id
+            .map(|request_id| request_id.to_string())
+            .unwrap_or_else(|| self.request_id())
     }
 
     // peer_id generates the peer id.
@@ -116,3 +264,135 @@ impl IDGenerator {
         format!("{}-{}-{}", self.ip, self.hostname, Uuid::new_v4())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_id_generates_a_sortable_uuid() {
+        let id_generator = IDGenerator::new("127.0.0.1".to_string(), "host".to_string(), false);
+        let request_id = id_generator.request_id();
+        assert!(uuid::Uuid::parse_str(&request_id).is_ok());
+        // Version 7 UUIDs lead with a millisecond timestamp, so they sort by creation time.
+        assert_eq!(
+            uuid::Uuid::parse_str(&request_id).unwrap().get_version_num(),
+            7
+        );
+    }
+
+    #[test]
+    fn correlation_id_returns_supplied_id_unchanged() {
+        let id_generator = IDGenerator::new("127.0.0.1".to_string(), "host".to_string(), false);
+        assert_eq!(
+            id_generator.correlation_id(Some("incoming-request-id")),
+            "incoming-request-id"
+        );
+    }
+
+    #[test]
+    fn correlation_id_falls_back_to_generated_request_id_when_none() {
+        let id_generator = IDGenerator::new("127.0.0.1".to_string(), "host".to_string(), false);
+        let request_id = id_generator.correlation_id(None);
+        assert!(uuid::Uuid::parse_str(&request_id).is_ok());
+    }
+
+    #[test]
+    fn should_parse_valid_algorithm_tagged_digest() {
+        let hex = "a".repeat(64);
+        let digest = format!("sha256:{}", hex);
+        let (algorithm, parsed_hex) = DigestAlgorithm::parse(&digest).unwrap();
+        assert_eq!(algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(parsed_hex, hex);
+    }
+
+    #[test]
+    fn should_reject_digest_missing_colon() {
+        let err = DigestAlgorithm::parse("deadbeef").unwrap_err();
+        assert!(matches!(err, DFError::UnsupportedDigestAlgorithm(_)));
+    }
+
+    #[test]
+    fn should_reject_unknown_algorithm_prefix() {
+        let err = DigestAlgorithm::parse("md5:deadbeef").unwrap_err();
+        assert!(matches!(err, DFError::UnsupportedDigestAlgorithm(_)));
+    }
+
+    #[test]
+    fn should_reject_wrong_hex_length() {
+        let err = DigestAlgorithm::parse("sha256:deadbeef").unwrap_err();
+        assert!(matches!(err, DFError::PieceDigestMismatch));
+    }
+
+    #[test]
+    fn should_reject_non_hex_chars() {
+        let bogus = format!("sha256:{}", "z".repeat(64));
+        let err = DigestAlgorithm::parse(&bogus).unwrap_err();
+        assert!(matches!(err, DFError::PieceDigestMismatch));
+    }
+
+    #[test]
+    fn task_id_accepts_legacy_opaque_digest_without_error() {
+        let id_generator = IDGenerator::new("127.0.0.1".to_string(), "host".to_string(), false);
+        id_generator
+            .task_id(
+                "https://example.com/file",
+                Some("not-an-algo-tagged-digest"),
+                None,
+                None,
+                1024,
+                Vec::new(),
+            )
+            .expect("legacy opaque digests must still be accepted");
+    }
+
+    #[test]
+    fn task_id_hashes_the_full_digest_string_for_compatibility() {
+        let id_generator = IDGenerator::new("127.0.0.1".to_string(), "host".to_string(), false);
+        let hex = "a".repeat(64);
+        let tagged_digest = format!("sha256:{}", hex);
+
+        let with_tagged_digest = id_generator
+            .task_id(
+                "https://example.com/file",
+                Some(&tagged_digest),
+                None,
+                None,
+                1024,
+                Vec::new(),
+            )
+            .unwrap();
+
+        // Hashing only the hex part (dropping the `sha256:` prefix) would collide with a
+        // caller that passed the bare hex as an opaque legacy digest. The two must
+        // produce different task ids, proving the full original string is what's hashed.
+        let with_bare_hex = id_generator
+            .task_id(
+                "https://example.com/file",
+                Some(&hex),
+                None,
+                None,
+                1024,
+                Vec::new(),
+            )
+            .unwrap();
+
+        assert_ne!(with_tagged_digest, with_bare_hex);
+    }
+
+    #[test]
+    fn task_id_rejects_malformed_algorithm_tagged_digest() {
+        let id_generator = IDGenerator::new("127.0.0.1".to_string(), "host".to_string(), false);
+        let err = id_generator
+            .task_id(
+                "https://example.com/file",
+                Some("sha256:deadbeef"),
+                None,
+                None,
+                1024,
+                Vec::new(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, DFError::PieceDigestMismatch));
+    }
+}